@@ -1,16 +1,15 @@
-// TODO: Make this module be able to handle complicated queries like in src/repo.rs:478
-
 use std::collections::VecDeque;
 use crate::helpers::sql::{escape_fts5_string, escape_like_pattern};
 
 #[derive(Debug, Eq, PartialEq)]
-enum Symbol {
+pub(crate) enum Symbol {
     /// A tag. E.g. `kick`
     Tag(String),
     /// A key-value pair. E.g. `inpath:res/audio/`
     InPath(String),
 }
 
+#[derive(Debug)]
 enum QueryConversionError {
     NoAssociatedFTSString,
     NoAssociatedWhereClause,
@@ -33,10 +32,24 @@ impl Symbol {
             )),
         }
     }
+
+    /// Convert this symbol into a standalone boolean SQL predicate.
+    fn to_sql(&self) -> String {
+        match self {
+            Symbol::Tag(_) => format!(
+                "id IN (SELECT rowid FROM items_fts WHERE items_fts MATCH '{}')",
+                self.to_fts_string()
+                    .expect("Symbol::Tag always has an FTS string"),
+            ),
+            Symbol::InPath(_) => self
+                .to_where_clause()
+                .expect("Symbol::InPath always has a WHERE clause"),
+        }
+    }
 }
 
 #[derive(Debug)]
-enum Expr {
+pub(crate) enum Expr {
     /// Represents AND between 2 terms: `a & b`
     And(Box<Expr>, Box<Expr>),
     /// Represents OR between 2 terms: `a | b`
@@ -47,16 +60,17 @@ enum Expr {
     Term(Symbol),
 }
 
-// impl Expr {
-//   fn to_where_clause(&self) -> String {
-//     match self {
-//       And(a, b) => {},
-//       Or(a, b) => {},
-//       Not(a) => {},
-//       Term(sym) => {},
-//     }
-//   }
-// }
+impl Expr {
+    /// Recursively compile this expression tree into a single boolean SQL `WHERE` clause.
+    pub(crate) fn to_sql(&self) -> String {
+        match self {
+            Expr::And(a, b) => format!("({} AND {})", a.to_sql(), b.to_sql()),
+            Expr::Or(a, b) => format!("({} OR {})", a.to_sql(), b.to_sql()),
+            Expr::Not(a) => format!("NOT ({})", a.to_sql()),
+            Expr::Term(sym) => sym.to_sql(),
+        }
+    }
+}
 
 /// Depth-first search iterator for an expression
 struct ExprDFSIterator<'a> {
@@ -92,6 +106,200 @@ impl<'a> Iterator for ExprDFSIterator<'a> {
     }
 }
 
+/// A single lexical token produced by [`tokenize`].
+#[derive(Debug, Eq, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    /// A bare tag (`kick`) or a `key:value` pair (`inpath:res/audio/`), not yet resolved to a
+    /// [`Symbol`].
+    Word(String),
+}
+
+/// An error produced while parsing a query string into an [`Expr`].
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum ParseError {
+    /// The query string was empty (or contained only whitespace).
+    EmptyQuery,
+    /// A `(` was never closed by a matching `)`.
+    UnterminatedGroup,
+    /// A `)` appeared with no matching `(`.
+    UnmatchedCloseParen,
+    /// An operator (`&`, `|`, `~`, `-`) was not followed by a term to apply to.
+    DanglingOperator(String),
+    /// The query ended while a term was still expected.
+    UnexpectedEnd,
+    /// Trailing input remained after a complete expression was parsed.
+    TrailingInput(String),
+    /// A `key:value` pair used a key that has no corresponding [`Symbol`] variant.
+    UnknownKey(String),
+}
+
+/// Split a query string into [`Token`]s. `( ) & | ~` are always operators, even mid-word; `-` is
+/// only a NOT operator when it starts a token, otherwise it's just part of the word.
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                tokens.push(Token::LParen);
+                chars.next();
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                chars.next();
+            }
+            '&' => {
+                tokens.push(Token::And);
+                chars.next();
+            }
+            '|' => {
+                tokens.push(Token::Or);
+                chars.next();
+            }
+            '~' | '-' => {
+                tokens.push(Token::Not);
+                chars.next();
+            }
+            _ => {
+                // `-` is only a NOT operator at the start of a token (handled by the arm
+                // above); once a word has begun, a `-` is just part of it (e.g. `inpath:a-b`).
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "()&|~".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Resolve a bare word token into a [`Symbol`].
+fn symbol_from_word(word: &str) -> Result<Symbol, ParseError> {
+    match word.split_once(':') {
+        Some(("inpath", value)) => Ok(Symbol::InPath(value.to_string())),
+        Some((key, _)) => Err(ParseError::UnknownKey(key.to_string())),
+        None => Ok(Symbol::Tag(word.to_string())),
+    }
+}
+
+/// Recursive-descent parser over a token stream. Precedence, loosest to tightest:
+/// `OR` < `AND` (including implicit juxtaposition) < `NOT` < grouping/terms.
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn advance(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_and()?;
+        while matches!(self.peek(), Some(Token::Or)) {
+            self.advance();
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, ParseError> {
+        let mut left = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.advance();
+                }
+                Some(Token::Or) | Some(Token::RParen) | None => break,
+                // Any other token starts a new term: treat juxtaposition as an implicit AND.
+                _ => {}
+            }
+            if matches!(self.peek(), Some(Token::Or) | Some(Token::RParen) | None) {
+                return Err(ParseError::DanglingOperator("&".to_string()));
+            }
+            let right = self.parse_unary()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.advance();
+                if matches!(
+                    self.peek(),
+                    Some(Token::And) | Some(Token::Or) | Some(Token::RParen) | None
+                ) {
+                    return Err(ParseError::DanglingOperator("~".to_string()));
+                }
+                let inner = self.parse_unary()?;
+                Ok(Expr::Not(Box::new(inner)))
+            }
+            Some(Token::LParen) => {
+                self.advance();
+                let inner = self.parse_or()?;
+                match self.advance() {
+                    Some(Token::RParen) => Ok(inner),
+                    _ => Err(ParseError::UnterminatedGroup),
+                }
+            }
+            Some(Token::Word(_)) => {
+                let word = match self.advance() {
+                    Some(Token::Word(word)) => word,
+                    _ => unreachable!("peeked a Word above"),
+                };
+                symbol_from_word(word).map(Expr::Term)
+            }
+            Some(Token::RParen) => Err(ParseError::UnmatchedCloseParen),
+            Some(other) => Err(ParseError::DanglingOperator(format!("{:?}", other))),
+            None => Err(ParseError::UnexpectedEnd),
+        }
+    }
+}
+
+/// Parse a query string (e.g. `a b -e inpath:1 | d e inpath:0`) into an [`Expr`] tree.
+pub(crate) fn parse_query(input: &str) -> Result<Expr, ParseError> {
+    let tokens = tokenize(input);
+    if tokens.is_empty() {
+        return Err(ParseError::EmptyQuery);
+    }
+
+    let mut parser = Parser { tokens: &tokens, pos: 0 };
+    let expr = parser.parse_or()?;
+
+    if parser.pos != tokens.len() {
+        return Err(ParseError::TrailingInput(format!(
+            "{:?}",
+            &tokens[parser.pos..]
+        )));
+    }
+
+    Ok(expr)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -131,4 +339,115 @@ mod tests {
             println!("{:?}", x);
         }
     }
+
+    #[test]
+    fn to_sql_compiles_the_example_tree() {
+        let expr = or(
+            and(and(tag("a"), tag("b")), and(not(tag("e")), inpath("1"))),
+            and(tag("d"), and(tag("e"), inpath("0"))),
+        );
+
+        let sql = expr.to_sql();
+
+        assert!(sql.contains(r#"tags:"a""#));
+        assert!(sql.contains(r#"path LIKE '1' ESCAPE '\'"#));
+        assert!(sql.contains(r#"path LIKE '0' ESCAPE '\'"#));
+    }
+
+    #[test]
+    fn to_sql_negates_tags_without_breaking_fts_match() {
+        let sql = not(tag("e")).to_sql();
+
+        // The NOT must wrap the whole subquery predicate, not the MATCH string itself.
+        assert_eq!(
+            sql,
+            r#"NOT (id IN (SELECT rowid FROM items_fts WHERE items_fts MATCH 'tags:"e"'))"#
+        );
+    }
+
+    #[test]
+    fn parse_query_compiles_the_example_query_to_sql() {
+        // The example query from the docs: `a b -e inpath:1 | d e inpath:0`.
+        // AND binds tighter than OR, so this is `(a & b & ~e & inpath:1) | (d & e & inpath:0)`.
+        let parsed = parse_query("a b -e inpath:1 | d e inpath:0").unwrap();
+
+        let sql = parsed.to_sql();
+
+        assert!(sql.contains(r#"tags:"a""#));
+        assert!(sql.contains(r#"tags:"b""#));
+        assert!(sql.contains(r#"tags:"d""#));
+        assert!(sql.contains("NOT (id IN"));
+        assert!(sql.contains(r#"path LIKE '1' ESCAPE '\'"#));
+        assert!(sql.contains(r#"path LIKE '0' ESCAPE '\'"#));
+        assert!(sql.contains(" OR "));
+    }
+
+    #[test]
+    fn parse_query_handles_parens_and_explicit_and() {
+        let parsed = parse_query("(a & b) | ~c").unwrap();
+        let expected = or(and(tag("a"), tag("b")), not(tag("c")));
+
+        assert_eq!(format!("{:?}", parsed), format!("{:?}", *expected));
+    }
+
+    #[test]
+    fn parse_query_keeps_hyphens_inside_a_word_literal() {
+        // A leading `-` negates, but a `-` inside an already-started word (e.g. a hyphenated
+        // path) is just part of that word, not a second NOT.
+        let parsed = parse_query("inpath:res/audio-dir").unwrap();
+        let expected = inpath("res/audio-dir");
+
+        assert_eq!(format!("{:?}", parsed), format!("{:?}", *expected));
+    }
+
+    #[test]
+    fn parse_query_rejects_empty_input() {
+        assert!(matches!(parse_query(""), Err(ParseError::EmptyQuery)));
+        assert!(matches!(parse_query("   "), Err(ParseError::EmptyQuery)));
+    }
+
+    #[test]
+    fn parse_query_rejects_unterminated_group() {
+        assert!(matches!(
+            parse_query("(a & b"),
+            Err(ParseError::UnterminatedGroup)
+        ));
+    }
+
+    #[test]
+    fn parse_query_rejects_unmatched_close_paren() {
+        assert!(matches!(
+            parse_query(")"),
+            Err(ParseError::UnmatchedCloseParen)
+        ));
+    }
+
+    #[test]
+    fn parse_query_rejects_trailing_input() {
+        assert!(matches!(
+            parse_query("a)"),
+            Err(ParseError::TrailingInput(_))
+        ));
+    }
+
+    #[test]
+    fn parse_query_rejects_dangling_operators() {
+        assert!(matches!(parse_query("a |"), Err(ParseError::UnexpectedEnd)));
+        assert!(matches!(
+            parse_query("~"),
+            Err(ParseError::DanglingOperator(op)) if op == "~"
+        ));
+        assert!(matches!(
+            parse_query("a &"),
+            Err(ParseError::DanglingOperator(op)) if op == "&"
+        ));
+    }
+
+    #[test]
+    fn parse_query_rejects_unknown_keys() {
+        assert!(matches!(
+            parse_query("bpm:120"),
+            Err(ParseError::UnknownKey(key)) if key == "bpm"
+        ));
+    }
 }
@@ -1,7 +1,17 @@
+use futures::Stream;
+use std::collections::{HashSet, VecDeque};
 use std::fs;
 use std::fs::{DirEntry, ReadDir};
-use std::io::Error;
+use std::hash::{Hash, Hasher};
+use std::io::{Error, Read};
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::{Duration, SystemTime};
 
 // pub enum Filter {
 //   ExcludeName(PathBuf),
@@ -55,6 +65,501 @@ where
   }
 }
 
+// ---------------------------------------------------------------------------
+// Parallel scanning with live progress reporting
+// ---------------------------------------------------------------------------
+
+/// A snapshot of scan progress, sent periodically over the channel passed to
+/// [`scan_dir_with_progress`].
+#[derive(Debug, Clone, Copy)]
+pub struct ProgressData {
+  pub entries_checked: usize,
+  pub entries_to_check: usize,
+}
+
+const PROGRESS_REPORT_INTERVAL: Duration = Duration::from_millis(150);
+const SCAN_WORKER_COUNT: usize = 4;
+
+/// Parallel variant of `scan_dir`, reporting progress over `progress_tx` as it goes. The
+/// returned `Vec<PathBuf>` is order-independent, since workers append to it concurrently.
+pub fn scan_dir_with_progress(
+  path: impl AsRef<Path>,
+  progress_tx: Sender<ProgressData>,
+) -> Result<Vec<PathBuf>, ScanError> {
+  let path = path.as_ref();
+
+  let metadata = path.metadata().map_err(ScanError::IOError)?;
+  if !metadata.is_dir() { return Err(ScanError::NotADirectory); }
+
+  let items = Arc::new(Mutex::new(Vec::new()));
+  let unscanned_dirs = Arc::new(Mutex::new(VecDeque::new()));
+  let entries_checked = Arc::new(AtomicUsize::new(0));
+  let entries_to_check = Arc::new(AtomicUsize::new(0));
+  let active_workers = Arc::new(AtomicUsize::new(0));
+
+  // seed the queue with the initial listing, same as scan_dir's first pass
+  let dir_iter = fs::read_dir(path).map_err(ScanError::IOError)?;
+  let mut initial_items = Vec::new();
+  let mut initial_dirs = Vec::new();
+  classify_dir_items(dir_iter.flatten(), &mut initial_items, &mut initial_dirs);
+  entries_to_check.fetch_add(initial_dirs.len(), Ordering::Relaxed);
+  items.lock().unwrap().extend(initial_items);
+  unscanned_dirs.lock().unwrap().extend(initial_dirs);
+
+  let stop_stats = Arc::new(AtomicBool::new(false));
+  let stats_thread = {
+    let entries_checked = Arc::clone(&entries_checked);
+    let entries_to_check = Arc::clone(&entries_to_check);
+    let stop_stats = Arc::clone(&stop_stats);
+    thread::spawn(move || {
+      while !stop_stats.load(Ordering::Relaxed) {
+        let _ = progress_tx.send(ProgressData {
+          entries_checked: entries_checked.load(Ordering::Relaxed),
+          entries_to_check: entries_to_check.load(Ordering::Relaxed),
+        });
+        thread::sleep(PROGRESS_REPORT_INTERVAL);
+      }
+    })
+  };
+
+  let workers: Vec<_> = (0..SCAN_WORKER_COUNT)
+    .map(|_| {
+      let items = Arc::clone(&items);
+      let unscanned_dirs = Arc::clone(&unscanned_dirs);
+      let entries_checked = Arc::clone(&entries_checked);
+      let entries_to_check = Arc::clone(&entries_to_check);
+      let active_workers = Arc::clone(&active_workers);
+
+      thread::spawn(move || loop {
+        // Mark ourselves active *before* popping, so a worker that is about to claim the last
+        // queued dir is already visible to others' "is anyone still working" check below --
+        // otherwise two workers could race between "queue just went empty" and "count it as
+        // active", and the rest of the pool would quit before the claimed dir is scanned.
+        active_workers.fetch_add(1, Ordering::SeqCst);
+        let next_dir = unscanned_dirs.lock().unwrap().pop_front();
+        let Some(dir) = next_dir else {
+          active_workers.fetch_sub(1, Ordering::SeqCst);
+          if active_workers.load(Ordering::SeqCst) == 0 && unscanned_dirs.lock().unwrap().is_empty() {
+            break;
+          }
+          thread::sleep(Duration::from_millis(5));
+          continue;
+        };
+
+        if let Ok(dir_iter) = fs::read_dir(&dir) {
+          let mut found_items = Vec::new();
+          let mut found_dirs = Vec::new();
+          classify_dir_items(dir_iter.flatten(), &mut found_items, &mut found_dirs);
+          entries_to_check.fetch_add(found_dirs.len(), Ordering::Relaxed);
+          items.lock().unwrap().extend(found_items);
+          unscanned_dirs.lock().unwrap().extend(found_dirs);
+        }
+        entries_checked.fetch_add(1, Ordering::Relaxed);
+        active_workers.fetch_sub(1, Ordering::SeqCst);
+      })
+    })
+    .collect();
+
+  for worker in workers {
+    let _ = worker.join();
+  }
+  stop_stats.store(true, Ordering::Relaxed);
+  let _ = stats_thread.join();
+
+  let items = Arc::try_unwrap(items)
+    .expect("all worker threads have joined")
+    .into_inner()
+    .unwrap();
+  Ok(items)
+}
+
+// ---------------------------------------------------------------------------
+// Symlink-following scans with cycle detection
+// ---------------------------------------------------------------------------
+
+/// How a followed symlink failed to resolve to a usable directory.
+#[derive(Debug, Eq, PartialEq)]
+pub enum SymlinkError {
+  /// The symlink's target is already on the current branch of the walk, or too many hops deep.
+  InfiniteRecursion,
+  /// The symlink's target does not exist.
+  NonExistentFile,
+}
+
+/// A symlink encountered by [`scan_dir_following_symlinks`] that could not be descended into.
+#[derive(Debug, Eq, PartialEq)]
+pub struct SymlinkInfo {
+  pub destination_path: PathBuf,
+  pub error: SymlinkError,
+}
+
+/// Maximum number of symlink hops to follow on any single branch of the walk before giving up.
+const MAX_SYMLINK_HOPS: usize = 20;
+
+/// Like [`scan_dir`], but descends into symlinked directories, reporting cycles and dangling
+/// symlinks back as [`SymlinkInfo`] entries instead of looping forever or panicking.
+pub fn scan_dir_following_symlinks(
+  path: impl AsRef<Path>,
+) -> Result<(Vec<PathBuf>, Vec<SymlinkInfo>), ScanError> {
+  let root = path.as_ref();
+
+  let metadata = root.metadata().map_err(ScanError::IOError)?;
+  if !metadata.is_dir() { return Err(ScanError::NotADirectory); }
+
+  let mut items = vec![];
+  let mut symlinks = vec![];
+  // Each stack entry carries the canonical ancestors on its own branch, so a cycle is only
+  // flagged when a directory reappears on the path that led to it, not anywhere in the tree.
+  let mut stack: Vec<(PathBuf, HashSet<PathBuf>)> = vec![(root.to_path_buf(), HashSet::new())];
+
+  while let Some((dir, ancestors)) = stack.pop() {
+    let Ok(dir_iter) = fs::read_dir(&dir) else { continue };
+
+    for entry in dir_iter.flatten() {
+      let entry_path = entry.path();
+      // `DirEntry::metadata` does not traverse symlinks, so a symlink's own entry type never
+      // tells us what it points to; check that separately before deciding how to handle it.
+      let is_symlink = entry
+        .file_type()
+        .map(|t| t.is_symlink())
+        .unwrap_or(false);
+
+      if !is_symlink {
+        let Ok(metadata) = entry.metadata() else { continue };
+        if metadata.is_dir() {
+          stack.push((entry_path, ancestors.clone()));
+        } else {
+          items.push(entry_path);
+        }
+        continue;
+      }
+
+      // Resolve what the symlink points to by following it explicitly.
+      let Ok(target_metadata) = fs::metadata(&entry_path) else {
+        symlinks.push(SymlinkInfo {
+          destination_path: entry_path,
+          error: SymlinkError::NonExistentFile,
+        });
+        continue;
+      };
+
+      if !target_metadata.is_dir() {
+        items.push(entry_path);
+        continue;
+      }
+
+      if ancestors.len() >= MAX_SYMLINK_HOPS {
+        symlinks.push(SymlinkInfo {
+          destination_path: entry_path,
+          error: SymlinkError::InfiniteRecursion,
+        });
+        continue;
+      }
+
+      match fs::canonicalize(&entry_path) {
+        Ok(canonical) if ancestors.contains(&canonical) => {
+          symlinks.push(SymlinkInfo {
+            destination_path: canonical,
+            error: SymlinkError::InfiniteRecursion,
+          });
+        }
+        Ok(canonical) => {
+          let mut next_ancestors = ancestors.clone();
+          next_ancestors.insert(canonical);
+          stack.push((entry_path, next_ancestors));
+        }
+        Err(_) => {
+          symlinks.push(SymlinkInfo {
+            destination_path: entry_path,
+            error: SymlinkError::NonExistentFile,
+          });
+        }
+      }
+    }
+  }
+
+  Ok((items, symlinks))
+}
+
+// ---------------------------------------------------------------------------
+// Gitignore-style exclusion filters with directory pruning
+// ---------------------------------------------------------------------------
+
+/// What to do with a directory once exclusion patterns have been checked against it.
+#[derive(Debug, Eq, PartialEq)]
+pub enum Decision {
+  /// The directory matches an exclude pattern; don't scan it at all.
+  Skip,
+  /// The directory doesn't match; recurse into it and keep checking its children.
+  Recurse,
+}
+
+/// A set of exclusion patterns evaluated against scanned paths, pruning whole directories
+/// before `scan_dir_excluding` descends into them rather than filtering out afterwards.
+pub struct ExcludeFilter {
+  patterns: Vec<String>,
+}
+
+impl ExcludeFilter {
+  pub fn new(patterns: impl IntoIterator<Item = String>) -> Self {
+    Self { patterns: patterns.into_iter().collect() }
+  }
+
+  fn matches(&self, path: &Path) -> bool {
+    self.patterns.iter().any(|pattern| {
+      if pattern.contains('/') || pattern.contains('\\') {
+        path_matches_pattern(path, pattern)
+      } else {
+        path
+          .file_name()
+          .and_then(|name| name.to_str())
+          .is_some_and(|name| glob_match(pattern, name))
+      }
+    })
+  }
+
+  /// Decide whether `dir` should be skipped or recursed into.
+  fn decide_dir(&self, dir: &Path) -> Decision {
+    if self.matches(dir) {
+      Decision::Skip
+    } else {
+      Decision::Recurse
+    }
+  }
+}
+
+/// Match a path-separator-containing exclude pattern against `path`, anchored to path components
+/// rather than a raw substring search.
+fn path_matches_pattern(path: &Path, pattern: &str) -> bool {
+  let pattern = pattern.replace('\\', "/");
+
+  if pattern.starts_with('/') {
+    return path.starts_with(Path::new(&pattern));
+  }
+
+  let pattern_components: Vec<&str> = pattern.split('/').filter(|s| !s.is_empty()).collect();
+  if pattern_components.is_empty() { return false; }
+
+  let path_components: Vec<&str> = path
+    .components()
+    .filter_map(|c| c.as_os_str().to_str())
+    .collect();
+  if pattern_components.len() > path_components.len() { return false; }
+
+  path_components.windows(pattern_components.len()).any(|window| {
+    window
+      .iter()
+      .zip(pattern_components.iter())
+      .all(|(part, pattern_part)| glob_match(pattern_part, part))
+  })
+}
+
+/// A tiny glob matcher supporting `*` (any run of characters, including none) and `?` (exactly
+/// one character). Good enough for exclude patterns like `*.tmp`; not a full gitignore grammar.
+fn glob_match(pattern: &str, text: &str) -> bool {
+  glob_match_bytes(pattern.as_bytes(), text.as_bytes())
+}
+
+fn glob_match_bytes(pattern: &[u8], text: &[u8]) -> bool {
+  match (pattern.first(), text.first()) {
+    (None, None) => true,
+    (Some(b'*'), _) => {
+      glob_match_bytes(&pattern[1..], text)
+        || (!text.is_empty() && glob_match_bytes(pattern, &text[1..]))
+    }
+    (Some(b'?'), Some(_)) => glob_match_bytes(&pattern[1..], &text[1..]),
+    (Some(p), Some(t)) if p == t => glob_match_bytes(&pattern[1..], &text[1..]),
+    _ => false,
+  }
+}
+
+/// Like [`scan_dir`], but skips files and prunes whole directories that match `exclude` before
+/// they are ever queued for scanning.
+pub fn scan_dir_excluding(
+  path: impl AsRef<Path>,
+  exclude: &ExcludeFilter,
+) -> Result<Vec<PathBuf>, ScanError> {
+  let path = path.as_ref();
+
+  let metadata = path.metadata().map_err(ScanError::IOError)?;
+  if !metadata.is_dir() { return Err(ScanError::NotADirectory); }
+
+  let mut items = vec![];
+  let mut unscanned_dirs = vec![];
+
+  let dir_iter = fs::read_dir(path).map_err(ScanError::IOError)?;
+  classify_dir_items_excluding(dir_iter.flatten(), exclude, &mut items, &mut unscanned_dirs);
+
+  while let Some(dir) = unscanned_dirs.pop() {
+    let Ok(dir_iter) = fs::read_dir(&dir) else { continue };
+    classify_dir_items_excluding(dir_iter.flatten(), exclude, &mut items, &mut unscanned_dirs);
+  }
+
+  Ok(items)
+}
+
+/// Like [`classify_dir_items`], but consults `exclude` before queueing a directory or keeping a
+/// file, pruning early instead of filtering the final list.
+fn classify_dir_items_excluding<T>(
+  dir_iter: T,
+  exclude: &ExcludeFilter,
+  items: &mut Vec<PathBuf>,
+  unscanned_dirs: &mut Vec<PathBuf>,
+)
+where
+  T: Iterator<Item = DirEntry>
+{
+  for entry in dir_iter {
+    let Ok(metadata) = entry.metadata() else { continue };
+    let entry_path = entry.path();
+
+    if metadata.is_dir() {
+      match exclude.decide_dir(&entry_path) {
+        Decision::Skip => continue,
+        Decision::Recurse => unscanned_dirs.push(entry_path),
+      }
+    } else if !exclude.matches(&entry_path) {
+      items.push(entry_path);
+    }
+  }
+}
+
+// ---------------------------------------------------------------------------
+// Rich file entries: size, mtime, and an optional content hash
+// ---------------------------------------------------------------------------
+
+/// A scanned file along with the metadata already read for it while walking the tree.
+#[derive(Debug)]
+pub struct FileEntry {
+  pub path: PathBuf,
+  pub size: u64,
+  pub modified: SystemTime,
+  pub hash: Option<String>,
+}
+
+/// Number of leading bytes hashed by [`partial_hash`] as a fast first pass.
+const PARTIAL_HASH_PREFIX_BYTES: usize = 64 * 1024;
+
+/// Like [`scan_dir`], but returns [`FileEntry`] values; pass `with_hash` to also compute a
+/// [`partial_hash`] of each file.
+pub fn scan_dir_entries(
+  path: impl AsRef<Path>,
+  with_hash: bool,
+) -> Result<Vec<FileEntry>, ScanError> {
+  let path = path.as_ref();
+
+  let metadata = path.metadata().map_err(ScanError::IOError)?;
+  if !metadata.is_dir() { return Err(ScanError::NotADirectory); }
+
+  let mut entries = vec![];
+  let mut unscanned_dirs = vec![];
+
+  let dir_iter = fs::read_dir(path).map_err(ScanError::IOError)?;
+  classify_dir_entries(dir_iter.flatten(), with_hash, &mut entries, &mut unscanned_dirs);
+
+  while !unscanned_dirs.is_empty() {
+    if let Ok(dir_iter) = fs::read_dir(unscanned_dirs.pop().unwrap()) {
+      classify_dir_entries(dir_iter.flatten(), with_hash, &mut entries, &mut unscanned_dirs);
+    }
+  }
+
+  Ok(entries)
+}
+
+/// Classify incoming DirEntries into rich [`FileEntry`] items or folders to be further scanned.
+fn classify_dir_entries<T>(
+  dir_iter: T,
+  with_hash: bool,
+  entries: &mut Vec<FileEntry>,
+  unscanned_dirs: &mut Vec<PathBuf>,
+)
+where
+  T: Iterator<Item = DirEntry>
+{
+  for entry in dir_iter {
+    if let Ok(metadata) = entry.metadata() {
+      if metadata.is_dir() {
+        unscanned_dirs.push(entry.path());
+      } else {
+        let path = entry.path();
+        let hash = if with_hash { partial_hash(&path).ok() } else { None };
+        entries.push(FileEntry {
+          path,
+          size: metadata.len(),
+          modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+          hash,
+        });
+      }
+    }
+  }
+}
+
+/// Hash the first [`PARTIAL_HASH_PREFIX_BYTES`] of a file together with its size, as a cheap
+/// first pass for duplicate detection.
+fn partial_hash(path: &Path) -> std::io::Result<String> {
+  let mut file = fs::File::open(path)?;
+  let size = file.metadata()?.len();
+
+  let mut prefix = vec![0u8; PARTIAL_HASH_PREFIX_BYTES];
+  let bytes_read = file.read(&mut prefix)?;
+  prefix.truncate(bytes_read);
+
+  let mut hasher = std::collections::hash_map::DefaultHasher::new();
+  prefix.hash(&mut hasher);
+  size.hash(&mut hasher);
+  Ok(format!("{:016x}", hasher.finish()))
+}
+
+// ---------------------------------------------------------------------------
+// Scanning as an async Stream
+// ---------------------------------------------------------------------------
+
+/// Lazily walks a directory tree, yielding each file path as soon as it is discovered.
+pub struct ScanStream {
+  stack: Vec<ReadDir>,
+}
+
+impl ScanStream {
+  pub fn new(path: impl AsRef<Path>) -> Result<Self, ScanError> {
+    let path = path.as_ref();
+
+    let metadata = path.metadata().map_err(ScanError::IOError)?;
+    if !metadata.is_dir() { return Err(ScanError::NotADirectory); }
+
+    let dir_iter = fs::read_dir(path).map_err(ScanError::IOError)?;
+    Ok(Self { stack: vec![dir_iter] })
+  }
+}
+
+impl Stream for ScanStream {
+  type Item = Result<PathBuf, ScanError>;
+
+  fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    loop {
+      let Some(dir_iter) = self.stack.last_mut() else {
+        return Poll::Ready(None);
+      };
+
+      match dir_iter.next() {
+        None => {
+          self.stack.pop();
+        }
+        Some(Err(err)) => return Poll::Ready(Some(Err(ScanError::IOError(err)))),
+        Some(Ok(entry)) => {
+          let Ok(metadata) = entry.metadata() else { continue };
+          if metadata.is_dir() {
+            if let Ok(child_iter) = fs::read_dir(entry.path()) {
+              self.stack.push(child_iter);
+            }
+          } else {
+            return Poll::Ready(Some(Ok(entry.path())));
+          }
+        }
+      }
+    }
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -77,4 +582,122 @@ mod tests {
       Err(e) => { dbg!(e); }
     }
   }
+
+  #[test]
+  fn glob_match_supports_star_and_question_mark() {
+    assert!(glob_match("*.tmp", "foo.tmp"));
+    assert!(!glob_match("*.tmp", "foo.tmp.bak"));
+    assert!(glob_match("node_modules", "node_modules"));
+    assert!(glob_match("a?c", "abc"));
+    assert!(!glob_match("a?c", "ac"));
+  }
+
+  #[test]
+  fn exclude_filter_prunes_matching_directories_before_recursing() {
+    let exclude = ExcludeFilter::new(["node_modules".to_string(), "*.tmp".to_string()]);
+
+    assert_eq!(
+      exclude.decide_dir(Path::new("/project/node_modules")),
+      Decision::Skip
+    );
+    assert_eq!(
+      exclude.decide_dir(Path::new("/project/src")),
+      Decision::Recurse
+    );
+    assert!(exclude.matches(Path::new("/project/cache.tmp")));
+  }
+
+  #[test]
+  fn exclude_filter_with_no_patterns_recurses_everything() {
+    let exclude = ExcludeFilter::new(Vec::<String>::new());
+    assert_eq!(
+      exclude.decide_dir(Path::new("/project/anything")),
+      Decision::Recurse
+    );
+  }
+
+  #[test]
+  fn scan_dir_with_progress_drains_queue_and_terminates() {
+    let dir = std::env::temp_dir().join("crate_scan_progress_test");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("a/aa")).unwrap();
+    fs::create_dir_all(dir.join("b")).unwrap();
+    fs::write(dir.join("a/one.txt"), "1").unwrap();
+    fs::write(dir.join("a/aa/two.txt"), "2").unwrap();
+    fs::write(dir.join("b/three.txt"), "3").unwrap();
+
+    let (progress_tx, progress_rx) = std::sync::mpsc::channel();
+    let items = scan_dir_with_progress(&dir, progress_tx).unwrap();
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    // The worker pool must have actually terminated (scan_dir_with_progress returned) and
+    // drained every queued directory rather than quitting early.
+    assert_eq!(items.len(), 3);
+
+    // The stats thread should also have stopped, so the channel is disconnected rather than
+    // still being fed.
+    while progress_rx.recv().is_ok() {}
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn scan_dir_following_symlinks_detects_a_real_cycle() {
+    let dir = std::env::temp_dir().join("crate_scan_symlink_cycle_test");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    // `loop` is a symlink to its own parent, so descending into it revisits an ancestor.
+    std::os::unix::fs::symlink(&dir, dir.join("loop")).unwrap();
+
+    let (_items, symlinks) = scan_dir_following_symlinks(&dir).unwrap();
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(symlinks.len(), 1);
+    assert_eq!(symlinks[0].error, SymlinkError::InfiniteRecursion);
+  }
+
+  #[test]
+  fn scan_dir_entries_reports_size_and_optional_hash() {
+    let dir = std::env::temp_dir().join("crate_scan_entries_test");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.txt"), "hello").unwrap();
+
+    let with_hash = scan_dir_entries(&dir, true).unwrap();
+    assert_eq!(with_hash.len(), 1);
+    assert_eq!(with_hash[0].size, 5);
+    assert!(with_hash[0].hash.is_some());
+
+    let without_hash = scan_dir_entries(&dir, false).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(without_hash.len(), 1);
+    assert_eq!(without_hash[0].size, 5);
+    assert_eq!(without_hash[0].hash, None);
+  }
+
+  #[test]
+  fn scan_stream_yields_every_file_lazily() {
+    let dir = std::env::temp_dir().join("crate_scan_stream_test");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("nested")).unwrap();
+    fs::write(dir.join("a.txt"), "a").unwrap();
+    fs::write(dir.join("nested/b.txt"), "b").unwrap();
+
+    let mut stream = ScanStream::new(&dir).unwrap();
+    let mut found = vec![];
+    futures::executor::block_on(async {
+      use futures::StreamExt;
+      while let Some(item) = stream.next().await {
+        found.push(item.unwrap());
+      }
+    });
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(found.len(), 2);
+    assert!(found.iter().any(|p| p.ends_with("a.txt")));
+    assert!(found.iter().any(|p| p.ends_with("nested/b.txt") || p.ends_with("nested\\b.txt")));
+  }
 }
@@ -1,39 +1,186 @@
+use crate::scan::{ScanError, ScanStream};
 use futures::{
-    channel::mpsc::{unbounded, UnboundedReceiver},
+    channel::{
+        mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
     SinkExt, StreamExt,
 };
-use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
-use std::path::Path;
-
-async fn async_watch(path: impl AsRef<Path>) -> notify::Result<()> {
-    let (mut tx, mut rx) = unbounded();
-
-    let mut watcher = RecommendedWatcher::new(
-        move |res| {
-            futures::executor::block_on(async {
-                tx.send(res).await.unwrap();
-            })
-        },
-        Config::default(),
-    )?;
-
-    watcher.watch(path.as_ref(), RecursiveMode::Recursive)?;
-
-    while let Some(res) = rx.next().await {
-        let res = res.unwrap();
-        println!("{:?}", res);
+use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::{Duration, Instant};
+
+/// How a path changed, coalesced from the raw `notify` event kinds we care about.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Removed,
+}
+
+/// A single item produced by [`watch_dir`]: either a path found while walking the existing
+/// contents of the directory, or a live change detected afterwards.
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// A path found during the initial scan.
+    Initial(PathBuf),
+    /// A path that changed after the initial scan completed.
+    Changed { path: PathBuf, kind: ChangeKind },
+}
+
+/// How long to wait for more events on the same path before forwarding the coalesced result, so
+/// a rapid create-then-write burst becomes a single `Changed` event.
+const DEBOUNCE_WINDOW: Duration = Duration::from_millis(300);
+
+fn change_kind(event: &Event) -> Option<ChangeKind> {
+    match event.kind {
+        EventKind::Create(_) => Some(ChangeKind::Created),
+        EventKind::Modify(_) => Some(ChangeKind::Modified),
+        EventKind::Remove(_) => Some(ChangeKind::Removed),
+        _ => None,
     }
+}
+
+/// Watch `path`, first streaming its existing contents (via [`ScanStream`]) as
+/// [`WatchEvent::Initial`] items, then transitioning to live [`WatchEvent::Changed`] events
+/// sourced from a `notify` watcher. This gives callers one unified async source for "initial
+/// contents, then incremental deltas" instead of a separate batch scan and watch API.
+pub async fn watch_dir(
+    path: impl AsRef<Path>,
+) -> Result<UnboundedReceiver<Result<WatchEvent, ScanError>>, ScanError> {
+    let path = path.as_ref().to_path_buf();
+    let mut scan_stream = ScanStream::new(&path)?;
+
+    let (mut tx, rx) = unbounded();
 
-    Ok(())
+    while let Some(item) = scan_stream.next().await {
+        if tx.send(item.map(WatchEvent::Initial)).await.is_err() {
+            // Receiver dropped before the initial scan even finished; nothing left to do.
+            return Ok(rx);
+        }
+    }
+
+    let (ready_tx, ready_rx) = oneshot::channel();
+    spawn_watcher(path, tx, ready_tx);
+    // Wait for the watcher to actually be registered before handing back `rx`, so a change made
+    // right after we return isn't missed. This awaits rather than blocks, so it doesn't stall the
+    // executor thread while the background thread does the (possibly slow, recursive) registration.
+    let _ = ready_rx.await;
+
+    Ok(rx)
 }
 
-fn main() {
-    let path = r"D:\Programming\rust-learning\temp";
-    println!("watching {}", path);
+/// Set up a `notify` watcher on `path` and forward debounced changes into `tx`, running until
+/// either the watcher errs or the receiving end is dropped. Watcher creation and registration
+/// happen on this background thread (they can be slow for large trees), but `ready_tx` only
+/// fires once registration has completed, so callers that wait on it never miss a change.
+fn spawn_watcher(
+    path: PathBuf,
+    mut tx: UnboundedSender<Result<WatchEvent, ScanError>>,
+    ready_tx: oneshot::Sender<()>,
+) {
+    std::thread::spawn(move || {
+        let (raw_tx, raw_rx) = std::sync::mpsc::channel::<notify::Result<Event>>();
 
-    futures::executor::block_on(async {
-        if let Err(e) = async_watch(path).await {
-            println!("error: {:?}", e)
+        let mut watcher = match RecommendedWatcher::new(
+            move |res| {
+                let _ = raw_tx.send(res);
+            },
+            Config::default(),
+        ) {
+            Ok(watcher) => watcher,
+            Err(_) => return,
+        };
+
+        if watcher.watch(&path, RecursiveMode::Recursive).is_err() {
+            return;
+        }
+
+        // Registration is done; wake up `watch_dir` so it can hand `rx` back to its caller.
+        let _ = ready_tx.send(());
+
+        // Keep the watcher alive for as long as this thread runs; dropping it would stop events.
+        let _watcher = watcher;
+        let mut pending: HashMap<PathBuf, (ChangeKind, Instant)> = HashMap::new();
+
+        loop {
+            match raw_rx.recv_timeout(DEBOUNCE_WINDOW) {
+                Ok(Ok(event)) => {
+                    if let Some(kind) = change_kind(&event) {
+                        for event_path in event.paths {
+                            pending.insert(event_path, (kind, Instant::now()));
+                        }
+                    }
+                }
+                Ok(Err(_)) | Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+
+            let ready: Vec<PathBuf> = pending
+                .iter()
+                .filter(|(_, (_, seen_at))| seen_at.elapsed() >= DEBOUNCE_WINDOW)
+                .map(|(path, _)| path.clone())
+                .collect();
+
+            for path in ready {
+                let Some((kind, _)) = pending.remove(&path) else { continue };
+                let sent = futures::executor::block_on(
+                    tx.send(Ok(WatchEvent::Changed { path, kind })),
+                );
+                if sent.is_err() {
+                    return;
+                }
+            }
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Poll `rx` until an item arrives or `timeout` elapses.
+    fn recv_within(
+        rx: &mut UnboundedReceiver<Result<WatchEvent, ScanError>>,
+        timeout: Duration,
+    ) -> Option<WatchEvent> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match rx.try_recv() {
+                Ok(item) => return Some(item.unwrap()),
+                Err(e) if e.is_closed() => return None,
+                Err(_) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(20)),
+                Err(_) => return None,
+            }
+        }
+    }
+
+    #[test]
+    fn watch_dir_yields_initial_items_then_a_coalesced_change() {
+        let dir = std::env::temp_dir().join("crate_watch_dir_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("existing.txt"), "a").unwrap();
+
+        let mut rx = futures::executor::block_on(watch_dir(&dir)).unwrap();
+
+        let initial = recv_within(&mut rx, Duration::from_secs(2)).expect("initial item");
+        assert!(matches!(initial, WatchEvent::Initial(p) if p.ends_with("existing.txt")));
+
+        // Two rapid writes to the same path should coalesce into a single debounced event.
+        fs::write(dir.join("new.txt"), "1").unwrap();
+        fs::write(dir.join("new.txt"), "12").unwrap();
+
+        let changed = recv_within(&mut rx, DEBOUNCE_WINDOW * 4).expect("changed item");
+        match changed {
+            WatchEvent::Changed { path, .. } => assert!(path.ends_with("new.txt")),
+            other => panic!("expected Changed, got {:?}", other),
+        }
+        assert!(recv_within(&mut rx, DEBOUNCE_WINDOW).is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}